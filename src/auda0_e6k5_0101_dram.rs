@@ -2,13 +2,21 @@ use anyhow::{Result, anyhow};
 use i2c_linux::I2c;
 use std::fs::File;
 
+use crate::rgb_controller::{RgbController, ZoneInfo};
+
 pub struct I2cDram {
+    bus: String,
     addresses: Vec<u16>,
     i2c: I2c<File>,
     led_count: u8,
+    zones: Vec<ZoneInfo>,
+    staged: Vec<(u8, u8, u8)>,
 }
 
 impl I2cDram {
+    /// ENE controller on-die temperature register.
+    const TEMP_REG: u16 = 0x1031;
+
     fn register_read(i2c: &mut I2c<File>, reg: u16) -> Result<u8> {
         i2c.smbus_write_word_data(0x0, ((reg << 8) & 0xFF00) | ((reg >> 8) & 0x00FF))?;
         Ok(i2c.smbus_read_byte_data(0x81)?)
@@ -26,9 +34,13 @@ impl I2cDram {
         Ok(())
     }
 
-    pub fn new(addresses: Vec<u16>) -> Result<I2cDram> {
-        let mut i2c = I2c::from_path("/dev/i2c-2")?;
-        for address in &addresses {
+    /// Open the bus and run the controller's probe/init sequence, returning the
+    /// opened bus and the LED count it reports. Shared by [`new`](Self::new) and
+    /// [`reinit`](RgbController::reinit) so re-enumeration replays the exact
+    /// same bring-up.
+    fn open(bus: &str, addresses: &[u16]) -> Result<(I2c<File>, u8)> {
+        let mut i2c = I2c::from_path(bus)?;
+        for address in addresses {
             i2c.smbus_set_slave_address(*address, false)?;
             i2c.smbus_read_byte()?;
 
@@ -46,24 +58,82 @@ impl I2cDram {
         for i in 0..64 {
             config_table[i as usize] = Self::register_read(&mut i2c, 0x1C00 + i)?;
         }
-        let led_count = config_table[2];
+        Ok((i2c, config_table[2]))
+    }
+
+    pub fn new(bus: &str, addresses: Vec<u16>) -> Result<I2cDram> {
+        let (i2c, led_count) = Self::open(bus, &addresses)?;
+
+        let zones = (0..led_count)
+            .map(|i| ZoneInfo::new(format!("dram-led-{i}")))
+            .collect();
 
         Ok(Self {
+            bus: bus.to_string(),
             addresses,
             i2c,
             led_count,
+            zones,
+            staged: vec![(0, 0, 0); led_count as usize],
         })
     }
 
-    pub fn set_led_colour(&mut self, r: u8, g: u8, b: u8) -> Result<()> {
+    /// Read the controller's on-die temperature (°C) from the first address.
+    pub fn read_temperature(&mut self) -> Result<f32> {
+        let address = *self
+            .addresses
+            .first()
+            .ok_or_else(|| anyhow!("no dram address configured"))?;
+        self.i2c.smbus_set_slave_address(address, false)?;
+        Ok(f32::from(Self::register_read(&mut self.i2c, Self::TEMP_REG)?))
+    }
+
+    fn write_staged(&mut self) -> Result<()> {
         for address in &self.addresses {
             self.i2c.smbus_set_slave_address(*address, false)?;
             Self::register_write(&mut self.i2c, 0x8020, 1)?;
             Self::register_write(&mut self.i2c, 0x80A0, 1)?;
             for i in 0..self.led_count {
+                let (r, g, b) = self.staged[i as usize];
                 Self::register_write_block(&mut self.i2c, 0x8100 + (3 * u16::from(i)), &[r, b, g])?;
             }
         }
         Ok(())
     }
 }
+
+impl RgbController for I2cDram {
+    fn zones(&self) -> &[ZoneInfo] {
+        &self.zones
+    }
+
+    fn name(&self) -> &str {
+        "dram"
+    }
+
+    fn set_zone_colour(&mut self, zone: usize, r: u8, g: u8, b: u8) -> Result<()> {
+        let slot = self
+            .staged
+            .get_mut(zone)
+            .ok_or_else(|| anyhow!("dram zone {zone} out of range"))?;
+        *slot = (r, g, b);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_staged()
+    }
+
+    fn reinit(&mut self) -> Result<()> {
+        let (i2c, led_count) = Self::open(&self.bus, &self.addresses)?;
+        self.i2c = i2c;
+        self.led_count = led_count;
+        // Preserve the staged colours across a re-enumeration, resizing only if
+        // the controller now reports a different LED count.
+        self.staged.resize(led_count as usize, (0, 0, 0));
+        self.zones = (0..led_count)
+            .map(|i| ZoneInfo::new(format!("dram-led-{i}")))
+            .collect();
+        Ok(())
+    }
+}
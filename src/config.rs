@@ -0,0 +1,321 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Runtime configuration loaded from a TOML file rather than baked into the
+/// source. The path is taken from the `--config <path>` CLI flag, falling back
+/// to `$RGBMAN_CONFIG`; if neither is set [`Config::default`] is used so the
+/// binary still runs with the historical built-in values.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// I2C bus the DRAM controller lives on, e.g. `/dev/i2c-2`.
+    pub bus: String,
+    /// DRAM SMBus slave addresses to drive.
+    pub addresses: Vec<u16>,
+    /// On/off windows evaluated by the state machine, in order.
+    pub schedules: Vec<Schedule>,
+    /// Colour/brightness mapping per named zone.
+    pub zones: Vec<ZoneConfig>,
+    /// Fade transition timing, easing, and gamma.
+    pub fade: Fade,
+    /// Optional sensor-reactive lighting. Absent means schedule-driven only.
+    pub sensor: Option<Sensor>,
+    /// Optional MQTT control/telemetry bridge. Absent disables it.
+    pub mqtt: Option<Mqtt>,
+}
+
+/// MQTT broker connection and topic layout.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Mqtt {
+    /// Broker host.
+    pub host: String,
+    /// Broker port.
+    pub port: u16,
+    /// MQTT client id.
+    pub client_id: String,
+    /// Topic prefix; commands land on `<prefix>/set`, state on `<prefix>/state`.
+    pub prefix: String,
+    /// Home Assistant MQTT discovery prefix; `None` disables discovery.
+    pub discovery_prefix: Option<String>,
+}
+
+impl Default for Mqtt {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "rgbman".to_string(),
+            prefix: "rgbman".to_string(),
+            discovery_prefix: Some("homeassistant".to_string()),
+        }
+    }
+}
+
+/// Sensor-reactive lighting: map a live temperature onto a colour gradient
+/// instead of following the time-of-day schedule.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Sensor {
+    /// Sampling source.
+    pub source: SensorSource,
+    /// Seconds between samples.
+    pub interval_s: u64,
+    /// Window length of the median deglitcher; an even value is rounded up to
+    /// the next odd one so a full window has a true median. Single-sample
+    /// outliers are rejected.
+    pub median_window: usize,
+    /// Smoothing factor of the exponential low-pass in `(0, 1]`; smaller is
+    /// smoother.
+    pub smoothing: f32,
+    /// Temperature (°C) mapped to the cold end of the gradient.
+    pub min_temp: f32,
+    /// Temperature (°C) mapped to the hot end of the gradient.
+    pub max_temp: f32,
+    /// RGB coefficients at `min_temp`.
+    pub cold_rgb: [f32; 3],
+    /// RGB coefficients at `max_temp`.
+    pub hot_rgb: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorSource {
+    /// CPU package temperature read from a `/sys/class/hwmon` input file.
+    Hwmon { path: String },
+    /// DRAM temperature read over I2C through the existing controller.
+    Dram,
+}
+
+impl Sensor {
+    /// Interpolate the cold→hot gradient at normalised position `t` in `[0, 1]`.
+    pub fn gradient(&self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let mut out = [0.0; 3];
+        for (i, slot) in out.iter_mut().enumerate() {
+            *slot = self.cold_rgb[i] + (self.hot_rgb[i] - self.cold_rgb[i]) * t;
+        }
+        out
+    }
+}
+
+impl Default for Sensor {
+    fn default() -> Self {
+        Self {
+            source: SensorSource::Hwmon {
+                path: "/sys/class/hwmon/hwmon0/temp1_input".to_string(),
+            },
+            interval_s: 2,
+            median_window: 5,
+            smoothing: 0.3,
+            min_temp: 30.0,
+            max_temp: 80.0,
+            cold_rgb: [0.0, 0.0, 255.0],
+            hot_rgb: [255.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Timing and perceptual shaping for `State::Fading` transitions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Fade {
+    /// Total wall-clock length of a fade, in milliseconds.
+    pub duration_ms: u64,
+    /// Easing curve applied to linear progress `t`.
+    pub easing: Easing,
+    /// Display gamma; the eased value `v` reaches the PWM channels as `v^gamma`.
+    pub gamma: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    /// `t*t*(3-2t)` — symmetric ease-in-out.
+    Smoothstep,
+    /// No shaping; `v = t`.
+    Linear,
+}
+
+impl Easing {
+    /// Map linear progress `t` in `[0, 1]` through the curve.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::Linear => t,
+        }
+    }
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            duration_ms: 2000,
+            easing: Easing::Smoothstep,
+            gamma: 2.2,
+        }
+    }
+}
+
+/// A time window mapping an hour range to a target [`mode`](Schedule::mode).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schedule {
+    /// First hour of the window (inclusive, 0..24).
+    pub start: u32,
+    /// Last hour of the window (exclusive, 0..=24).
+    pub end: u32,
+    /// Target mode while the current hour is inside the window.
+    pub mode: ScheduleMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleMode {
+    On,
+    Off,
+}
+
+/// Per-zone colour coefficients applied to the shared brightness `x`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneConfig {
+    /// Name matched against a controller zone, e.g. `fans` or `dram-led-0`.
+    pub name: String,
+    /// Red/green/blue coefficients multiplied by `x` before truncation to `u8`.
+    pub rgb: [f32; 3],
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bus: "/dev/i2c-2".to_string(),
+            addresses: vec![0x71, 0x73],
+            schedules: vec![
+                Schedule {
+                    start: 0,
+                    end: 8,
+                    mode: ScheduleMode::Off,
+                },
+                Schedule {
+                    start: 8,
+                    end: 18,
+                    mode: ScheduleMode::On,
+                },
+                Schedule {
+                    start: 18,
+                    end: 24,
+                    mode: ScheduleMode::Off,
+                },
+            ],
+            zones: vec![
+                ZoneConfig {
+                    name: "dram".to_string(),
+                    rgb: [89.0, 60.0, 46.0],
+                },
+                ZoneConfig {
+                    name: "fans".to_string(),
+                    rgb: [54.0, 28.0, 15.0],
+                },
+            ],
+            fade: Fade::default(),
+            sensor: None,
+            mqtt: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from `path`, or fall back to the defaults when no path
+    /// is configured via CLI flag or `$RGBMAN_CONFIG`.
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let path = path.or_else(|| env::var_os("RGBMAN_CONFIG").map(PathBuf::from));
+        match path {
+            Some(path) => Self::from_path(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Target mode for `hour` (0..24): the mode of the first schedule window
+    /// that contains the hour, or `None` when no window matches.
+    pub fn mode_for_hour(&self, hour: u32) -> Option<ScheduleMode> {
+        self.schedules
+            .iter()
+            .find(|s| (s.start..s.end).contains(&hour))
+            .map(|s| s.mode)
+    }
+
+    fn from_path(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        let config: Config = toml::from_str(&text)
+            .with_context(|| format!("parsing config {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Colour coefficients for `zone`. An exact name match wins; otherwise the
+    /// zone's family (the part before the first `-`, so `dram-led-0` → `dram`)
+    /// is tried, and failing that the zone is driven white.
+    pub fn zone_rgb(&self, zone: &str) -> [f32; 3] {
+        let family = zone.split('-').next().unwrap_or(zone);
+        self.zones
+            .iter()
+            .find(|z| z.name == zone)
+            .or_else(|| self.zones.iter().find(|z| z.name == family))
+            .map(|z| z.rgb)
+            .unwrap_or([255.0, 255.0, 255.0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_schedule_reproduces_baseline_off_overnight() {
+        let config = Config::default();
+        assert_eq!(config.mode_for_hour(12), Some(ScheduleMode::On));
+        assert_eq!(config.mode_for_hour(3), Some(ScheduleMode::Off));
+        assert_eq!(config.mode_for_hour(20), Some(ScheduleMode::Off));
+    }
+
+    #[test]
+    fn zone_rgb_falls_back_to_family_then_white() {
+        let config = Config::default();
+        assert_eq!(config.zone_rgb("dram"), [89.0, 60.0, 46.0]);
+        // `dram-led-0` has no exact entry but matches the `dram` family.
+        assert_eq!(config.zone_rgb("dram-led-0"), [89.0, 60.0, 46.0]);
+        assert_eq!(config.zone_rgb("unknown"), [255.0, 255.0, 255.0]);
+    }
+
+    #[test]
+    fn smoothstep_is_symmetric_and_pinned_at_the_ends() {
+        assert_eq!(Easing::Smoothstep.apply(0.0), 0.0);
+        assert_eq!(Easing::Smoothstep.apply(1.0), 1.0);
+        assert_eq!(Easing::Smoothstep.apply(0.5), 0.5);
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+    }
+
+    #[test]
+    fn gradient_interpolates_and_clamps() {
+        let sensor = Sensor::default();
+        assert_eq!(sensor.gradient(0.0), sensor.cold_rgb);
+        assert_eq!(sensor.gradient(1.0), sensor.hot_rgb);
+        // Out-of-range positions clamp to the ends.
+        assert_eq!(sensor.gradient(2.0), sensor.hot_rgb);
+    }
+}
+
+/// Parse the optional `--config <path>` flag from the process arguments.
+pub fn config_arg() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
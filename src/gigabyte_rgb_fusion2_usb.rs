@@ -1,15 +1,23 @@
 use anyhow::{Context, Result};
 use hidapi::{HidApi, HidDevice, HidResult};
 
+use crate::rgb_controller::{RgbController, ZoneInfo};
+
 pub struct Fusion2Argb {
     dev: HidDevice,
     effect_mask: u8,
+    zones: Vec<ZoneInfo>,
+    staged: (u8, u8, u8),
 }
 
 impl Fusion2Argb {
     const RID: u8 = 0xCC;
 
-    pub fn new() -> Result<Self> {
+    /// Scan the HID bus for the Fusion 2 controller, open it, and replay the
+    /// IT5711 reset/apply bring-up. Shared by [`new`](Self::new) and
+    /// [`reinit`](RgbController::reinit) so hot re-enumeration is identical to a
+    /// cold start.
+    fn open() -> Result<HidDevice> {
         let api = HidApi::new()?;
         let wanted_dev = api
             .device_list()
@@ -31,9 +39,15 @@ impl Fusion2Argb {
         Self::send64(&dev, 0x28, 0xFF, 0x07)?; // IT5711 "apply" after reset
         Self::send64(&dev, 0x31, 0x00, 0x00)?; // beat off
 
+        Ok(dev)
+    }
+
+    pub fn new() -> Result<Self> {
         Ok(Self {
-            dev,
+            dev: Self::open()?,
             effect_mask: 0,
+            zones: vec![ZoneInfo::new("fans")],
+            staged: (0, 0, 0),
         })
     }
 
@@ -46,7 +60,8 @@ impl Fusion2Argb {
         dev.send_feature_report(&buf)
     }
 
-    pub fn set_led_colour(&mut self, r: u8, g: u8, b: u8) -> Result<()> {
+    fn write_staged(&mut self) -> Result<()> {
+        let (r, g, b) = self.staged;
         self.effect_mask |= 0x01 | 0x02 | 0x08 | 0x10;
         self.effect_mask |= 0x10;
         let pkt = PktEffect::all_leds(Self::RID)
@@ -62,6 +77,34 @@ impl Fusion2Argb {
     }
 }
 
+impl RgbController for Fusion2Argb {
+    fn zones(&self) -> &[ZoneInfo] {
+        &self.zones
+    }
+
+    fn name(&self) -> &str {
+        "fusion2"
+    }
+
+    fn set_zone_colour(&mut self, zone: usize, r: u8, g: u8, b: u8) -> Result<()> {
+        if zone != 0 {
+            anyhow::bail!("fusion2 zone {zone} out of range");
+        }
+        self.staged = (r, g, b);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_staged()
+    }
+
+    fn reinit(&mut self) -> Result<()> {
+        self.dev = Self::open()?;
+        self.effect_mask = 0;
+        Ok(())
+    }
+}
+
 mod effect {
     pub const STATIC: u8 = 1;
 }
@@ -1,7 +1,8 @@
 use anyhow::Result;
 use chrono::{Local, Timelike};
-use http_body_util::Full;
-use hyper::body::{Bytes, Incoming};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Request, Response};
@@ -9,16 +10,25 @@ use hyper_util::rt::TokioIo;
 use std::convert::Infallible;
 use std::env;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
-use tracing::{Subscriber, error, info};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{BroadcastStream, WatchStream};
+use tracing::{Subscriber, error, info, warn};
 use tracing_subscriber::{EnvFilter, Registry, prelude::*};
 
 mod auda0_e6k5_0101_dram;
+mod config;
 mod gigabyte_rgb_fusion2_usb;
+mod mqtt;
+mod rgb_controller;
+mod sensor;
 use auda0_e6k5_0101_dram as dram;
+use config::{Config, ScheduleMode};
 use gigabyte_rgb_fusion2_usb as fusion2;
+use rgb_controller::RgbController;
+use std::sync::Arc;
 
 #[derive(PartialEq, Clone, Debug)]
 enum Mode {
@@ -26,14 +36,56 @@ enum Mode {
     Off,
 }
 
+impl From<ScheduleMode> for Mode {
+    fn from(mode: ScheduleMode) -> Self {
+        match mode {
+            ScheduleMode::On => Mode::On,
+            ScheduleMode::Off => Mode::Off,
+        }
+    }
+}
+
+/// Health of a single managed controller, published on the telemetry channel.
+#[derive(PartialEq, Clone, Debug)]
+struct Health {
+    name: String,
+    online: bool,
+}
+
+impl Mode {
+    /// Stable lowercase slug used in telemetry payloads.
+    fn slug(&self) -> &'static str {
+        match self {
+            Mode::On => "on",
+            Mode::Off => "off",
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 enum State {
-    Fading(Mode, f32),
+    /// A time-based fade toward `Mode`. The `Instant` is the fade's start; it is
+    /// `None` until the first tick stamps it, so a fade requested over the wire
+    /// is timed from when the state machine picks it up rather than from when it
+    /// was sent.
+    Fading(Mode, Option<Instant>),
     Turning(Mode),
     Idle(Mode),
     SetColourThen(f32, Box<State>),
 }
 
+impl State {
+    /// Stable slug describing the current state for telemetry, e.g. `fading-on`.
+    fn describe(&self) -> String {
+        match self {
+            State::Fading(mode, _) => format!("fading-{}", mode.slug()),
+            State::Turning(mode) => format!("turning-{}", mode.slug()),
+            State::Idle(mode) => format!("idle-{}", mode.slug()),
+            State::SetColourThen(_, _) => "setting-colour".to_string(),
+        }
+    }
+}
+
 fn init_tracing() {
     let filter = EnvFilter::new("rgbman=info");
     // Build either journald or fmt logger, boxed as trait object
@@ -57,27 +109,175 @@ fn init_tracing() {
     tracing::subscriber::set_global_default(subscriber).unwrap();
 }
 
-async fn run_rgb_server(new_led_state: &mut tokio::sync::watch::Receiver<f32>) -> Result<()> {
+/// Constructor for a controller, retried while a device that was missing at
+/// startup has never been opened; a device that drops off later is brought
+/// back through [`RgbController::reinit`] instead.
+type Factory = Box<dyn Fn() -> Result<Box<dyn RgbController>> + Send>;
+
+/// A controller plus the supervision state the server keeps for it: its
+/// constructor (for cold re-enumeration), the live instance if present, and
+/// whether its last write failed.
+struct ManagedDevice {
+    name: &'static str,
+    factory: Factory,
+    ctrl: Option<Box<dyn RgbController>>,
+    degraded: bool,
+}
+
+impl ManagedDevice {
+    fn new(name: &'static str, factory: Factory) -> Self {
+        let ctrl = match factory() {
+            Ok(ctrl) => Some(ctrl),
+            Err(e) => {
+                warn!(device = name, "initial enumeration failed: {e:?}");
+                None
+            }
+        };
+        let degraded = ctrl.is_none();
+        Self {
+            name,
+            factory,
+            ctrl,
+            degraded,
+        }
+    }
+
+    fn online(&self) -> bool {
+        self.ctrl.is_some() && !self.degraded
+    }
+
+    /// Push the current brightness/temperature to this device, marking it
+    /// degraded if any write fails so the supervisor re-enumerates it.
+    fn apply(&mut self, config: &Config, x: f32) {
+        let Some(ctrl) = self.ctrl.as_mut() else {
+            return;
+        };
+        let result = (|| {
+            for zone in 0..ctrl.zones().len() {
+                // In sensor mode `x` is the normalised temperature driving the
+                // gradient; otherwise it is the shared brightness scaling each
+                // zone's configured colour.
+                let (r, g, b) = match &config.sensor {
+                    Some(sensor) => {
+                        let c = sensor.gradient(x);
+                        (c[0] as u8, c[1] as u8, c[2] as u8)
+                    }
+                    None => {
+                        let base = config.zone_rgb(&ctrl.zones()[zone].name);
+                        ((base[0] * x) as u8, (base[1] * x) as u8, (base[2] * x) as u8)
+                    }
+                };
+                ctrl.set_zone_colour(zone, r, g, b)?;
+            }
+            ctrl.flush()
+        })();
+        if let Err(e) = result {
+            if !self.degraded {
+                warn!(device = self.name, "write failed, marking degraded: {e:?}");
+            }
+            self.degraded = true;
+        }
+    }
+
+    /// Attempt to bring a degraded device back: re-enumerate from scratch if it
+    /// was never opened, otherwise `reinit` the existing instance in place.
+    /// Returns `true` when the device transitioned back to healthy.
+    fn recover(&mut self) -> bool {
+        if self.online() {
+            return false;
+        }
+        let result = match self.ctrl.as_mut() {
+            Some(ctrl) => ctrl.reinit(),
+            None => (self.factory)().map(|ctrl| self.ctrl = Some(ctrl)),
+        };
+        match result {
+            Ok(()) => {
+                info!(device = self.name, "device recovered");
+                self.degraded = false;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Publish the current online/offline state of every managed device on the
+/// health telemetry channel.
+fn publish_health(devices: &[ManagedDevice], health: &tokio::sync::watch::Sender<Vec<Health>>) {
+    let snapshot = devices
+        .iter()
+        .map(|d| Health {
+            name: d.name.to_string(),
+            online: d.online(),
+        })
+        .collect();
+    let _ = health.send(snapshot);
+}
+
+async fn run_rgb_server(
+    config: Arc<Config>,
+    new_led_state: &mut tokio::sync::watch::Receiver<f32>,
+    health: tokio::sync::watch::Sender<Vec<Health>>,
+) -> Result<()> {
     info!("rgb server: starting...");
-    let mut dram = dram::I2cDram::new(vec![0x71, 0x73])?;
-    let mut fans = fusion2::Fusion2Argb::new()?;
+    let dram_config = config.clone();
+    let mut devices = vec![
+        ManagedDevice::new(
+            "dram",
+            Box::new(move || {
+                Ok(Box::new(dram::I2cDram::new(
+                    &dram_config.bus,
+                    dram_config.addresses.clone(),
+                )?) as Box<dyn RgbController>)
+            }),
+        ),
+        ManagedDevice::new(
+            "fusion2",
+            Box::new(|| Ok(Box::new(fusion2::Fusion2Argb::new()?) as Box<dyn RgbController>)),
+        ),
+    ];
 
     info!(message = "rgb server ready",);
+    publish_health(&devices, &health);
+
+    // Current brightness/temperature, retained across reconnects so a recovered
+    // device is immediately restored to the live value.
+    let mut x = *new_led_state.borrow();
+    let mut recovery = tokio::time::interval(Duration::from_secs(10));
+    recovery.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     loop {
-        new_led_state.changed().await?;
-        let x = *new_led_state.borrow();
-        fans.set_led_colour((54.0 * x) as u8, (28.0 * x) as u8, (15.0 * x) as u8)?;
-        dram.set_led_colour((89.0 * x) as u8, (60.0 * x) as u8, (46.0 * x) as u8)?;
+        tokio::select! {
+            changed = new_led_state.changed() => {
+                changed?;
+                x = *new_led_state.borrow();
+                for device in devices.iter_mut() {
+                    device.apply(&config, x);
+                }
+            }
+            _ = recovery.tick() => {
+                for device in devices.iter_mut() {
+                    if device.recover() {
+                        device.apply(&config, x);
+                    }
+                }
+            }
+        }
+        publish_health(&devices, &health);
     }
 }
 
 async fn run_state_machine(
+    config: Arc<Config>,
     starting_state: State,
     mut long_wait_when_idle: bool,
     send_rgb_value: tokio::sync::watch::Sender<f32>,
+    state_events: tokio::sync::broadcast::Sender<State>,
 ) -> Result<()> {
     let mut state = starting_state;
+    // Last telemetry slug published, so repeated fade ticks don't flood the
+    // channel with identical events.
+    let mut last_published: Option<String> = None;
     loop {
         let old_state = state.clone();
         state = match state {
@@ -85,19 +285,24 @@ async fn run_state_machine(
                 send_rgb_value.send(x)?;
                 *new_state
             }
-            State::Fading(mode, raw_x) => {
-                let x = match mode {
-                    Mode::On => 1.0 - raw_x,
-                    Mode::Off => raw_x,
+            State::Fading(mode, start) => {
+                let start = start.unwrap_or_else(Instant::now);
+                let duration = Duration::from_millis(config.fade.duration_ms);
+                let t = (start.elapsed().as_secs_f32() / duration.as_secs_f32()).clamp(0.0, 1.0);
+                let eased = config.fade.easing.apply(t);
+                // `eased` runs 0->1; `On` brightens, `Off` dims.
+                let v = match mode {
+                    Mode::On => eased,
+                    Mode::Off => 1.0 - eased,
                 };
+                // Gamma-correct so equal perceptual steps reach the PWM channels.
+                let x = v.powf(config.fade.gamma);
 
-                if raw_x > 0.08 {
-                    if (raw_x - 1.0).abs() > 0.01 {
-                        tokio::time::sleep(Duration::from_millis(80)).await;
-                    }
-                    State::SetColourThen(x * 0.2, Box::new(State::Fading(mode, raw_x * 0.2)))
-                } else {
+                if t >= 1.0 {
                     State::Turning(mode)
+                } else {
+                    tokio::time::sleep(Duration::from_millis(80)).await;
+                    State::SetColourThen(x, Box::new(State::Fading(mode, Some(start))))
                 }
             }
             State::Turning(mode) => match mode {
@@ -112,57 +317,100 @@ async fn run_state_machine(
                     tokio::time::sleep(Duration::from_secs(5 * 60)).await;
                 }
                 let hour = Local::now().hour();
-                if (8..18).contains(&hour) {
-                    if mode == Mode::Off {
-                        State::Fading(Mode::On, 1.0)
-                    } else {
-                        tokio::time::sleep(Duration::from_secs(5 * 60)).await;
-                        State::Turning(Mode::On)
+                match config.mode_for_hour(hour).map(Mode::from) {
+                    Some(target) => {
+                        if mode != target {
+                            State::Fading(target, None)
+                        } else {
+                            tokio::time::sleep(Duration::from_secs(5 * 60)).await;
+                            State::Turning(target)
+                        }
                     }
-                } else if hour >= 18 {
-                    if mode == Mode::On {
-                        State::Fading(Mode::Off, 1.0)
-                    } else {
+                    None => {
                         tokio::time::sleep(Duration::from_secs(5 * 60)).await;
-                        State::Turning(Mode::Off)
+                        State::Turning(mode)
                     }
-                } else {
-                    tokio::time::sleep(Duration::from_secs(5 * 60)).await;
-                    State::Turning(mode)
                 }
             }
         };
         if state != old_state {
             info!(message = "state change", old = ?old_state, new = ?state);
+            // Publish every automatic transition so the web UI and MQTT see what
+            // the state machine is doing, not just command-driven fades. The
+            // transient `SetColourThen` brightness carrier isn't a user-visible
+            // mode, and identical slugs are coalesced.
+            if !matches!(state, State::SetColourThen(_, _)) {
+                let slug = state.describe();
+                if last_published.as_deref() != Some(slug.as_str()) {
+                    let _ = state_events.send(state.clone());
+                    last_published = Some(slug);
+                }
+            }
         }
     }
 }
 
+/// Wrap `body` into the boxed body type every route returns, so a route can
+/// reply with either a one-shot [`Full`] or a streaming [`StreamBody`].
+fn full(body: impl Into<Bytes>) -> BoxBody<Bytes, Infallible> {
+    Full::new(body.into()).boxed()
+}
+
+/// Render one Server-Sent Event frame: a named `event` carrying JSON `data`.
+fn sse_frame(event: &str, data: String) -> Result<Frame<Bytes>, Infallible> {
+    Ok(Frame::data(Bytes::from(format!("event: {event}\ndata: {data}\n\n"))))
+}
+
 async fn handle(
     req: Request<Incoming>,
     tx: tokio::sync::broadcast::Sender<State>,
-) -> Result<Response<Full<Bytes>>, Infallible> {
+    events_tx: tokio::sync::broadcast::Sender<State>,
+    rgb_rx: tokio::sync::watch::Receiver<f32>,
+    health_rx: tokio::sync::watch::Receiver<Vec<Health>>,
+) -> Result<Response<BoxBody<Bytes, Infallible>>, Infallible> {
     let path = req.uri().path();
 
     match path {
         "/start_on" => {
-            let _ = tx.send(State::Fading(Mode::On, 1.0));
-            Ok(Response::new(Full::new(Bytes::from("Starting: ON"))))
+            let _ = tx.send(State::Fading(Mode::On, None));
+            Ok(Response::new(full("Starting: ON")))
         }
         "/start_off" => {
-            let _ = tx.send(State::Fading(Mode::Off, 1.0));
-            Ok(Response::new(Full::new(Bytes::from("Starting: OFF"))))
+            let _ = tx.send(State::Fading(Mode::Off, None));
+            Ok(Response::new(full("Starting: OFF")))
+        }
+        "/stop" => Ok(Response::new(full("Stopped state machine"))),
+        "/events" => {
+            // Stream every state change and brightness value to the browser as
+            // named SSE events, so the page reflects what the state machine is
+            // actually doing instead of firing blind into the channel.
+            let states = BroadcastStream::new(events_tx.subscribe())
+                .filter_map(Result::ok)
+                .map(|state| sse_frame("state", format!("{{\"state\":\"{}\"}}", state.describe())));
+            let brightness = WatchStream::new(rgb_rx)
+                .map(|x| sse_frame("brightness", format!("{{\"brightness\":{x}}}")));
+            let health = WatchStream::new(health_rx).map(|devices| {
+                let items = devices
+                    .iter()
+                    .map(|d| format!("{{\"name\":\"{}\",\"online\":{}}}", d.name, d.online))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                sse_frame("health", format!("[{items}]"))
+            });
+            let body = StreamBody::new(states.merge(brightness).merge(health)).boxed();
+            Ok(Response::builder()
+                .header("Content-Type", "text/event-stream")
+                .header("Cache-Control", "no-cache")
+                .body(body)
+                .unwrap())
         }
-        "/stop" => Ok(Response::new(Full::new(Bytes::from(
-            "Stopped state machine",
-        )))),
         "/" => {
             let html = include_str!("index.html");
-            Ok(Response::new(Full::new(Bytes::from(html))))
+            Ok(Response::new(full(html)))
         }
         _ => Ok(Response::builder()
             .status(404)
-            .body(Full::new(Bytes::from("Not found")))
+            .body(full("Not found"))
             .unwrap()),
     }
 }
@@ -170,42 +418,95 @@ async fn handle(
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     init_tracing();
+    let config = Arc::new(Config::load(config::config_arg())?);
     let (tx, _rx) = broadcast::channel(8);
+    // Telemetry channel: the state machine publishes every transition here for
+    // the web UI and MQTT, kept separate from `tx` so it can't feed back into
+    // the command-driven restart loop below.
+    let (events_tx, _events_rx) = broadcast::channel(8);
     let (rgb_tx, mut rgb_rx) = tokio::sync::watch::channel(0.0);
+    let (health_tx, health_rx) = tokio::sync::watch::channel(Vec::<Health>::new());
 
+    let rgb_config = config.clone();
     tokio::spawn(async move {
         loop {
-            match run_rgb_server(&mut rgb_rx).await {
+            match run_rgb_server(rgb_config.clone(), &mut rgb_rx, health_tx.clone()).await {
                 Ok(()) => break,
                 Err(e) => error!("Error: {e:?}"),
             }
         }
     });
 
-    let tx1 = tx.clone();
-    tokio::spawn(async move {
-        let tx = tx1.clone();
-        let mut rx = tx.subscribe();
-        let rgb_tx = rgb_tx.clone();
-        let mut last_task = None;
-        loop {
-            let rgb_tx = rgb_tx.clone();
-            if last_task.is_none() {
-                last_task = Some(tokio::spawn(async {
-                    run_state_machine(State::Turning(Mode::On), false, rgb_tx).await
-                }));
-                continue;
+    if config.mqtt.is_some() {
+        let mqtt_config = config.clone();
+        let state_tx = tx.clone();
+        let events_rx_src = events_tx.clone();
+        let brightness_rx = rgb_tx.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match mqtt::run_mqtt(
+                    mqtt_config.clone(),
+                    state_tx.clone(),
+                    events_rx_src.subscribe(),
+                    brightness_rx.clone(),
+                )
+                .await
+                {
+                    Ok(()) => break,
+                    Err(e) => error!("Error: {e:?}"),
+                }
             }
-            if let Ok(new_state) = rx.recv().await {
-                if let Some(last_task) = last_task {
-                    last_task.abort();
+        });
+    }
+
+    // A receiver handed to each HTTP connection so `/events` can stream live
+    // brightness; taken before `rgb_tx` is moved into the driver task below.
+    let http_rgb_rx = rgb_tx.subscribe();
+
+    if config.sensor.is_some() {
+        // Sensor-reactive mode drives the RGB server directly from live
+        // temperature instead of the time-of-day state machine.
+        let sensor_config = config.clone();
+        tokio::spawn(async move {
+            loop {
+                match sensor::run_sensor(sensor_config.clone(), rgb_tx.clone()).await {
+                    Ok(()) => break,
+                    Err(e) => error!("Error: {e:?}"),
                 }
-                last_task = Some(tokio::spawn(async {
-                    run_state_machine(new_state, true, rgb_tx).await
-                }));
             }
-        }
-    });
+        });
+    } else {
+        let tx1 = tx.clone();
+        let sm_config = config.clone();
+        let events_tx = events_tx.clone();
+        tokio::spawn(async move {
+            let tx = tx1.clone();
+            let mut rx = tx.subscribe();
+            let rgb_tx = rgb_tx.clone();
+            let mut last_task = None;
+            loop {
+                let rgb_tx = rgb_tx.clone();
+                let events_tx = events_tx.clone();
+                if last_task.is_none() {
+                    let config = sm_config.clone();
+                    last_task = Some(tokio::spawn(async move {
+                        run_state_machine(config, State::Turning(Mode::On), false, rgb_tx, events_tx)
+                            .await
+                    }));
+                    continue;
+                }
+                if let Ok(new_state) = rx.recv().await {
+                    if let Some(last_task) = last_task {
+                        last_task.abort();
+                    }
+                    let config = sm_config.clone();
+                    last_task = Some(tokio::spawn(async move {
+                        run_state_machine(config, new_state, true, rgb_tx, events_tx).await
+                    }));
+                }
+            }
+        });
+    }
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     let listener = TcpListener::bind(addr).await?;
@@ -214,10 +515,16 @@ async fn main() -> Result<()> {
         let (stream, _) = listener.accept().await?;
         let io = TokioIo::new(stream);
         let tx = tx.clone();
+        let events_tx = events_tx.clone();
+        let rgb_rx = http_rgb_rx.clone();
+        let health_rx = health_rx.clone();
 
         let service = service_fn(move |req| {
             let tx = tx.clone();
-            async move { handle(req, tx).await }
+            let events_tx = events_tx.clone();
+            let rgb_rx = rgb_rx.clone();
+            let health_rx = health_rx.clone();
+            async move { handle(req, tx, events_tx, rgb_rx, health_rx).await }
         });
 
         tokio::spawn(async move {
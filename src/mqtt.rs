@@ -0,0 +1,116 @@
+use anyhow::Result;
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::{Mode, State};
+
+/// Connect to the broker and bridge the controller onto retained topics:
+/// `<prefix>/set` accepts on/off commands (fed into the same `broadcast` the
+/// HTTP layer uses), `<prefix>/state` publishes the current [`State`], and
+/// `<prefix>/brightness` publishes the live brightness `x`, scaled to a 0..=255
+/// level, on every change. A
+/// Home Assistant light discovery config is published so the device appears
+/// automatically.
+pub async fn run_mqtt(
+    config: Arc<Config>,
+    state_tx: broadcast::Sender<State>,
+    mut state_rx: broadcast::Receiver<State>,
+    mut brightness_rx: watch::Receiver<f32>,
+) -> Result<()> {
+    let Some(mqtt) = config.mqtt.as_ref() else {
+        return Ok(());
+    };
+
+    let mut options = MqttOptions::new(&mqtt.client_id, &mqtt.host, mqtt.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut eventloop) = AsyncClient::new(options, 16);
+
+    let set_topic = format!("{}/set", mqtt.prefix);
+    let state_topic = format!("{}/state", mqtt.prefix);
+    let brightness_topic = format!("{}/brightness", mqtt.prefix);
+
+    client.subscribe(&set_topic, QoS::AtLeastOnce).await?;
+
+    if let Some(discovery_prefix) = &mqtt.discovery_prefix {
+        let topic = format!("{discovery_prefix}/light/{}/config", mqtt.client_id);
+        let payload = discovery_payload(mqtt, &state_topic, &set_topic, &brightness_topic);
+        client
+            .publish(topic, QoS::AtLeastOnce, true, payload)
+            .await?;
+    }
+
+    info!(message = "mqtt bridge ready", host = %mqtt.host, port = mqtt.port);
+
+    loop {
+        tokio::select! {
+            // Republish state changes on their retained topic.
+            Ok(state) = state_rx.recv() => {
+                let _ = client
+                    .publish(&state_topic, QoS::AtLeastOnce, true, state.describe())
+                    .await;
+            }
+            // Republish brightness on change.
+            Ok(()) = brightness_rx.changed() => {
+                let x = *brightness_rx.borrow();
+                // Scale the 0..=1 brightness onto the 0..=255 range HA expects
+                // from `brightness_scale`, so it doesn't quantize to 0/1.
+                let level = (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+                let _ = client
+                    .publish(&brightness_topic, QoS::AtLeastOnce, true, format!("{level}"))
+                    .await;
+            }
+            // Handle inbound commands from the broker.
+            event = eventloop.poll() => match event {
+                Ok(Event::Incoming(Incoming::Publish(p))) if p.topic == set_topic => {
+                    if let Some(mode) = parse_command(&p.payload) {
+                        let _ = state_tx.send(State::Fading(mode, None));
+                    } else {
+                        warn!("mqtt: unrecognised command on {set_topic}");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("mqtt eventloop error: {e:?}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+}
+
+/// Map a `<prefix>/set` payload (`on`/`off`, case-insensitive) to a [`Mode`].
+fn parse_command(payload: &[u8]) -> Option<Mode> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    match text.to_ascii_lowercase().as_str() {
+        "on" => Some(Mode::On),
+        "off" => Some(Mode::Off),
+        _ => None,
+    }
+}
+
+/// Build the Home Assistant MQTT light discovery config.
+fn discovery_payload(
+    mqtt: &crate::config::Mqtt,
+    state_topic: &str,
+    set_topic: &str,
+    brightness_topic: &str,
+) -> String {
+    format!(
+        concat!(
+            "{{\"name\":\"rgbman\",\"unique_id\":\"{id}\",",
+            "\"command_topic\":\"{set}\",\"state_topic\":\"{state}\",",
+            "\"payload_on\":\"on\",\"payload_off\":\"off\",",
+            "\"state_value_template\":\"{{{{ 'off' if value.endswith('-off') ",
+            "else 'on' }}}}\",",
+            "\"brightness_state_topic\":\"{bright}\",\"brightness_scale\":255}}"
+        ),
+        id = mqtt.client_id,
+        set = set_topic,
+        state = state_topic,
+        bright = brightness_topic,
+    )
+}
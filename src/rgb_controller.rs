@@ -0,0 +1,41 @@
+use anyhow::Result;
+
+/// Metadata describing a single independently addressable lighting zone of an
+/// [`RgbController`]. A controller exposes one `ZoneInfo` per zone it can drive
+/// so the server loop can address each zone without knowing the device layout.
+pub struct ZoneInfo {
+    pub name: String,
+}
+
+impl ZoneInfo {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+/// A lighting backend that owns one or more zones.
+///
+/// Colours are staged per zone with [`set_zone_colour`](RgbController::set_zone_colour)
+/// and only pushed to the hardware on [`flush`](RgbController::flush); this lets
+/// the server update every zone of a device and commit them together in one
+/// flush, and keeps the write path out of the per-zone setter.
+pub trait RgbController {
+    /// The zones this controller can drive, in the order expected by
+    /// [`set_zone_colour`](RgbController::set_zone_colour).
+    fn zones(&self) -> &[ZoneInfo];
+
+    /// A short stable name for this controller.
+    fn name(&self) -> &str;
+
+    /// Stage `zone`'s colour. Returns an error for an out-of-range zone.
+    fn set_zone_colour(&mut self, zone: usize, r: u8, g: u8, b: u8) -> Result<()>;
+
+    /// Commit the staged colours to the hardware, reporting whether the write
+    /// reached the device so the server can mark it degraded on failure.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Re-enumerate and re-open the underlying device, restoring it from a
+    /// degraded state without disturbing the staged colours. Called
+    /// periodically by the server while the device is missing.
+    fn reinit(&mut self) -> Result<()>;
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::auda0_e6k5_0101_dram as dram;
+use crate::config::{Config, SensorSource};
+
+/// Median-of-N deglitcher: keeps the last `capacity` samples in a ring buffer
+/// and emits their median, which rejects single-sample outliers (e.g. a spike
+/// from a flaky read) before they reach the colour mapping.
+pub struct Deglitcher {
+    window: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl Deglitcher {
+    pub fn new(capacity: usize) -> Self {
+        // Force an odd window so a full buffer has a single true median and an
+        // outlier can never tie the vote.
+        let capacity = capacity.max(1) | 1;
+        Self {
+            window: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Push `sample` and return the current median of the window. While the
+    /// buffer is still filling it can hold an even number of samples, so the two
+    /// central values are averaged rather than biasing toward the upper one.
+    pub fn push(&mut self, sample: f32) -> f32 {
+        if self.window.len() == self.capacity {
+            self.window.pop_front();
+        }
+        self.window.push_back(sample);
+        let mut sorted: Vec<f32> = self.window.iter().copied().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        }
+    }
+}
+
+/// First-order exponential low-pass that smooths the deglitched signal.
+pub struct LowPass {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl LowPass {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, sample: f32) -> f32 {
+        let next = match self.value {
+            Some(prev) => prev + self.alpha * (sample - prev),
+            None => sample,
+        };
+        self.value = Some(next);
+        next
+    }
+}
+
+/// A live temperature source, sampled once per tick.
+enum Source {
+    Hwmon(String),
+    Dram(dram::I2cDram),
+}
+
+impl Source {
+    fn open(config: &Config) -> Result<Self> {
+        let sensor = config
+            .sensor
+            .as_ref()
+            .context("sensor mode not configured")?;
+        match &sensor.source {
+            SensorSource::Hwmon { path } => Ok(Source::Hwmon(path.clone())),
+            SensorSource::Dram => Ok(Source::Dram(dram::I2cDram::new(
+                &config.bus,
+                config.addresses.clone(),
+            )?)),
+        }
+    }
+
+    fn sample(&mut self) -> Result<f32> {
+        match self {
+            Source::Hwmon(path) => {
+                let raw = std::fs::read_to_string(path.as_str())
+                    .with_context(|| format!("reading hwmon {path}"))?;
+                // hwmon temperatures are reported in millidegrees Celsius.
+                Ok(raw.trim().parse::<f32>()? / 1000.0)
+            }
+            Source::Dram(dram) => dram.read_temperature(),
+        }
+    }
+}
+
+/// Sample the configured sensor at a fixed interval, pass readings through the
+/// median deglitcher and exponential low-pass, and publish the normalised
+/// temperature in `[0, 1]` so the RGB server can map it onto its gradient.
+pub async fn run_sensor(config: Arc<Config>, send_temp: watch::Sender<f32>) -> Result<()> {
+    let sensor = config
+        .sensor
+        .as_ref()
+        .context("sensor mode not configured")?;
+    let mut source = Source::open(&config)?;
+    let mut deglitcher = Deglitcher::new(sensor.median_window);
+    let mut low_pass = LowPass::new(sensor.smoothing);
+    let interval = Duration::from_secs(sensor.interval_s.max(1));
+    let span = (sensor.max_temp - sensor.min_temp).max(f32::EPSILON);
+
+    info!(message = "sensor mode active", ?sensor.source);
+
+    loop {
+        match source.sample() {
+            Ok(raw) => {
+                let median = deglitcher.push(raw);
+                let smoothed = low_pass.update(median);
+                let t = ((smoothed - sensor.min_temp) / span).clamp(0.0, 1.0);
+                send_temp.send(t)?;
+            }
+            Err(e) => warn!("sensor sample failed: {e:?}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deglitcher_rejects_single_sample_outlier() {
+        let mut d = Deglitcher::new(5);
+        for _ in 0..4 {
+            d.push(20.0);
+        }
+        // A lone spike must not move the emitted value off the steady level.
+        assert_eq!(d.push(900.0), 20.0);
+    }
+
+    #[test]
+    fn deglitcher_forces_odd_window() {
+        // An even request is rounded up so the full window has a true median.
+        let mut d = Deglitcher::new(4);
+        for v in [1.0, 2.0, 3.0, 4.0, 5.0] {
+            d.push(v);
+        }
+        assert_eq!(d.push(6.0), 4.0);
+    }
+
+    #[test]
+    fn deglitcher_averages_two_central_samples_while_filling() {
+        let mut d = Deglitcher::new(5);
+        d.push(10.0);
+        // Two samples buffered: median is their average, not the upper one.
+        assert_eq!(d.push(20.0), 15.0);
+    }
+
+    #[test]
+    fn low_pass_seeds_on_first_sample_then_smooths() {
+        let mut lp = LowPass::new(0.5);
+        assert_eq!(lp.update(10.0), 10.0);
+        assert_eq!(lp.update(20.0), 15.0);
+    }
+}